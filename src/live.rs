@@ -31,11 +31,11 @@ pub fn module_status_line(input: &str) -> IResult<&str, KernelModule> {
             &space,
             alpha1,
             &space,
-            &alphanumeric1, // TODO: Parse this as an address, e.g. '0xffffffffc0a0c000'
+            &alphanumeric1,
             pair(alpha0, not_line_ending), // TODO: Handle this more elegantly
         ))(input)?;
 
-    dbg!(module_name, module_size, refs, dependents, state, location);
+    let address = u64::from_str_radix(location.trim_start_matches("0x"), 16).unwrap_or(0);
 
     Ok((
         input,
@@ -60,6 +60,7 @@ pub fn module_status_line(input: &str) -> IResult<&str, KernelModule> {
                 "Unloading" => ModuleState::Unloading,
                 _ => panic!("Unknown module state: {}", state),
             },
+            address,
         },
     ))
 }
@@ -78,17 +79,37 @@ pub struct KernelModule {
     refs: u32,
     dependents: Option<Vec<String>>,
     state: ModuleState,
-    // TODO: parse address
-    // address: Option<u64>,
+    address: u64,
+}
+
+impl KernelModule {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn is_live(&self) -> bool {
+        matches!(self.state, ModuleState::Live)
+    }
+
+    pub(crate) fn refs(&self) -> u32 {
+        self.refs
+    }
+
+    /// Modules currently using this one, as reported in the "Used by" column.
+    pub(crate) fn dependents(&self) -> &[String] {
+        self.dependents.as_deref().unwrap_or(&[])
+    }
+
+    /// The module's load address, e.g. `0xffffffffc0a0c000`.
+    pub(crate) fn address(&self) -> u64 {
+        self.address
+    }
 }
 
 pub fn parse_module_listing(data: &str) -> Vec<KernelModule> {
     many0(terminated(module_status_line, line_ending))(data)
-        .map(|(_, module)| {
-            dbg!(&module);
-            module
-        })
         .unwrap()
+        .1
 }
 
 #[cfg(test)]
@@ -1,12 +1,15 @@
 use anyhow::Result;
 use clap::{Arg, Command};
+use flate2::read::GzDecoder;
 use glob::glob;
-use object::{File as ObjectFile, Object, ObjectSymbol};
+use object::{File as ObjectFile, Object, ObjectSection, ObjectSymbol};
 use std::{
     collections,
+    ffi::CString,
     fs::{self, File},
     io::Read,
-    path::PathBuf,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
 };
 use xz::read::XzDecoder;
 use zstd::decode_all;
@@ -19,6 +22,15 @@ struct ModuleBrief {
     path: String,
     provides_symbols: Vec<String>,
     references_symbols: Vec<String>,
+    /// Module names from the `.modinfo` `depends=` entry, comma-separated in the section.
+    depends: Vec<String>,
+    /// Raw `softdep=` entries, e.g. "pre: foo post: bar".
+    softdep: Vec<String>,
+    /// Modalias patterns this module declares via `alias=`, e.g. "pci:v00008086d*".
+    alias: Vec<String>,
+    vermagic: Option<String>,
+    /// Declared `parm=` entries as (name, description).
+    parm: Vec<(String, String)>,
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, PartialOrd, Ord)]
@@ -33,74 +45,221 @@ struct SymbolBrief {
     direction: SymbolDirection,
 }
 
-fn resolve_dependency_tree(
-    all_modules: Vec<ModuleBrief>,
-    for_module_name: String,
-) -> Vec<ModuleBrief> {
-    // Perform a topological sort on the dependency graph to get the correct order of modules to be loaded
-    let mut sorted_modules: Vec<ModuleBrief> = Vec::new();
+/// A module set with its dependency edges built once, so resolving the load
+/// order for many targets (as `depmod` does) doesn't redo the symbol/`.modinfo`
+/// edge construction per target.
+struct DependencyGraph {
+    modules: Vec<ModuleBrief>,
+    index_of: collections::HashMap<String, usize>,
+    /// dependencies[i] = indices of modules that module i depends on.
+    dependencies: Vec<Vec<usize>>,
+}
 
-    // Get the module we are trying to load
-    let for_module = all_modules
-        .iter()
-        .find(|m| m.name == for_module_name)
-        .unwrap();
+impl DependencyGraph {
+    /// Build the graph from `all_modules`. Edges come from each module's
+    /// `.modinfo` `depends=` list when present, falling back to symbol
+    /// matching via a symbol -> provider index so edge construction is
+    /// O(total symbols) instead of re-walking `all_modules` per module.
+    fn build(all_modules: Vec<ModuleBrief>) -> Self {
+        let index_of: collections::HashMap<String, usize> = all_modules
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.name.clone(), i))
+            .collect();
 
-    // Get all the modules that the module we are trying to load references
-    let referenced_modules: Vec<ModuleBrief> = all_modules
-        .iter()
-        .filter(|m| {
-            for_module
-                .references_symbols
-                .iter()
-                .any(|s| m.provides_symbols.contains(s))
-        })
-        .cloned()
-        .collect();
+        let normalized_index_of: collections::HashMap<String, usize> = all_modules
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (strip_module_suffix(&m.name), i))
+            .collect();
 
-    // Recursively resolve the dependency tree for each of the referenced modules
-    for referenced_module in referenced_modules {
-        sorted_modules.append(&mut resolve_dependency_tree(
-            all_modules.clone(),
-            referenced_module.name,
-        ));
+        let mut symbol_provider: collections::HashMap<String, usize> = collections::HashMap::new();
+        for (i, m) in all_modules.iter().enumerate() {
+            for symbol in &m.provides_symbols {
+                symbol_provider.entry(symbol.clone()).or_insert(i);
+            }
+        }
+
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); all_modules.len()];
+        for (i, m) in all_modules.iter().enumerate() {
+            if !m.depends.is_empty() {
+                for dep in &m.depends {
+                    if let Some(&j) = normalized_index_of.get(&strip_module_suffix(dep)) {
+                        if j != i {
+                            dependencies[i].push(j);
+                        }
+                    }
+                }
+            } else {
+                for symbol in &m.references_symbols {
+                    if let Some(&j) = symbol_provider.get(symbol) {
+                        if j != i {
+                            dependencies[i].push(j);
+                        }
+                    }
+                }
+            }
+        }
+
+        DependencyGraph {
+            modules: all_modules,
+            index_of,
+            dependencies,
+        }
     }
 
-    // Add the module we are trying to load to the end of the list
-    sorted_modules.push(for_module.clone());
+    /// Resolve the load order for `for_module_name` via Kahn's algorithm over
+    /// the transitive closure of its dependencies, restricted to the modules
+    /// actually needed rather than the whole module set.
+    fn resolve(&self, for_module_name: &str) -> Result<Vec<ModuleBrief>> {
+        let target_index = *self
+            .index_of
+            .get(for_module_name)
+            .ok_or_else(|| anyhow::anyhow!("module '{}' not found", for_module_name))?;
 
-    // Remove duplicates from the list but preserve the order
+        // Restrict the sort to the transitive closure of the target module.
+        let mut closure: collections::HashSet<usize> = collections::HashSet::new();
+        let mut stack = vec![target_index];
+        while let Some(i) = stack.pop() {
+            if closure.insert(i) {
+                for &dep in &self.dependencies[i] {
+                    stack.push(dep);
+                }
+            }
+        }
 
-    let mut seen: collections::HashSet<String> = collections::HashSet::new();
-    let mut unique_sorted_modules: Vec<ModuleBrief> = Vec::new();
+        // Forward adjacency (dependency -> dependent) and in-degree, within the closure.
+        let mut adjacency: collections::HashMap<usize, Vec<usize>> = collections::HashMap::new();
+        let mut in_degree: collections::HashMap<usize, usize> =
+            closure.iter().map(|&i| (i, 0)).collect();
 
-    for module in sorted_modules {
-        let module_name = module.name.clone();
-        if !seen.contains(&module_name) {
-            unique_sorted_modules.push(module);
-            seen.insert(module_name);
+        for &i in &closure {
+            for &dep in &self.dependencies[i] {
+                if closure.contains(&dep) {
+                    adjacency.entry(dep).or_default().push(i);
+                    *in_degree.get_mut(&i).unwrap() += 1;
+                }
+            }
         }
+
+        // Kahn's algorithm: seed with modules that have no unmet dependencies.
+        let mut queue: collections::VecDeque<usize> = (0..self.modules.len())
+            .filter(|i| closure.contains(i) && in_degree[i] == 0)
+            .collect();
+
+        let mut order: Vec<usize> = Vec::new();
+        while let Some(i) = queue.pop_front() {
+            order.push(i);
+            if let Some(dependents) = adjacency.get(&i) {
+                for &d in dependents {
+                    let degree = in_degree.get_mut(&d).unwrap();
+                    *degree -= 1;
+                    if *degree == 0 {
+                        queue.push_back(d);
+                    }
+                }
+            }
+        }
+
+        if order.len() != closure.len() {
+            let cyclic: Vec<String> = closure
+                .iter()
+                .filter(|i| in_degree[i] > 0)
+                .map(|&i| self.modules[i].name.clone())
+                .collect();
+            return Err(anyhow::anyhow!(
+                "dependency cycle detected among: {}",
+                cyclic.join(", ")
+            ));
+        }
+
+        Ok(order.into_iter().map(|i| self.modules[i].clone()).collect())
     }
+}
 
-    unique_sorted_modules
+/// The module compression formats this tool can decompress.
+#[derive(Debug, PartialEq, Eq)]
+enum ModuleFormat {
+    Raw,
+    Gzip,
+    Xz,
+    Zstd,
+    Lz4,
+    Unknown,
 }
 
-fn read_to_module(path: PathBuf) -> Result<ModuleBrief> {
-    // println!("[Debug] filetype for {}: {}", &path.to_str().unwrap(), infer::get_from_path(&path).unwrap().unwrap().mime_type());
+/// Detect a module's compression format, combining the `infer` MIME sniff
+/// with the file extension and a magic-byte fallback so a mislabeled or
+/// extensionless module still gets identified.
+fn detect_module_format(path: &Path, data: &[u8]) -> ModuleFormat {
+    if let Some(kind) = infer::get(data) {
+        match kind.mime_type() {
+            "application/x-executable" | "application/vnd.microsoft.portable-executable" => {
+                return ModuleFormat::Raw
+            }
+            "application/gzip" => return ModuleFormat::Gzip,
+            "application/x-xz" => return ModuleFormat::Xz,
+            "application/zstd" => return ModuleFormat::Zstd,
+            "application/x-lz4" => return ModuleFormat::Lz4,
+            _ => {}
+        }
+    }
 
-    let binary_data: Vec<u8> = match infer::get_from_path(&path).unwrap().unwrap().mime_type() {
-        "application/x-executable" | "application/vnd.microsoft.portable-executable" => {
-            fs::read(&path)?
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => return ModuleFormat::Gzip,
+        Some("xz") => return ModuleFormat::Xz,
+        Some("zst") => return ModuleFormat::Zstd,
+        Some("lz4") => return ModuleFormat::Lz4,
+        Some("ko") => return ModuleFormat::Raw,
+        _ => {}
+    }
+
+    match data {
+        [0x1f, 0x8b, ..] => ModuleFormat::Gzip,
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => ModuleFormat::Xz,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => ModuleFormat::Zstd,
+        [0x04, 0x22, 0x4d, 0x18, ..] => ModuleFormat::Lz4,
+        [0x7f, b'E', b'L', b'F', ..] => ModuleFormat::Raw,
+        _ => ModuleFormat::Unknown,
+    }
+}
+
+/// Decompress a kernel module image regardless of which compression format
+/// (or none) it was stored in. The format list lives here alone so every
+/// caller that needs a module's raw ELF bytes goes through one place.
+fn decompress_module(path: &Path) -> Result<Vec<u8>> {
+    let raw = fs::read(path)?;
+
+    match detect_module_format(path, &raw) {
+        ModuleFormat::Raw => Ok(raw),
+        ModuleFormat::Gzip => {
+            let mut decoder = GzDecoder::new(&raw[..]);
+            let mut out = Vec::new();
+            decoder.read_to_end(&mut out)?;
+            Ok(out)
         }
-        "application/zstd" => decode_all(File::open(&path)?)?,
-        "application/x-xz" => {
-            let decoder = XzDecoder::new(File::open(&path)?);
-            decoder.bytes().collect::<Result<Vec<u8>, _>>()?
+        ModuleFormat::Xz => {
+            let mut decoder = XzDecoder::new(&raw[..]);
+            let mut out = Vec::new();
+            decoder.read_to_end(&mut out)?;
+            Ok(out)
         }
-        _ => {
-            panic!("Unknown file type for {}", path.to_str().unwrap());
+        ModuleFormat::Zstd => Ok(decode_all(&raw[..])?),
+        ModuleFormat::Lz4 => {
+            let mut decoder = lz4::Decoder::new(&raw[..])?;
+            let mut out = Vec::new();
+            decoder.read_to_end(&mut out)?;
+            Ok(out)
         }
-    };
+        ModuleFormat::Unknown => Err(anyhow::anyhow!(
+            "{}: unrecognized or corrupt module image",
+            path.display()
+        )),
+    }
+}
+
+fn read_to_module(path: PathBuf) -> Result<ModuleBrief> {
+    let binary_data = decompress_module(&path)?;
 
     let obj_file = ObjectFile::parse(&*binary_data)?;
 
@@ -123,6 +282,40 @@ fn read_to_module(path: PathBuf) -> Result<ModuleBrief> {
         })
         .collect();
 
+    let modinfo = obj_file
+        .section_by_name(".modinfo")
+        .and_then(|section| section.data().ok())
+        .map(parse_modinfo_section)
+        .unwrap_or_default();
+
+    let mut depends = Vec::new();
+    let mut softdep = Vec::new();
+    let mut alias = Vec::new();
+    let mut vermagic = None;
+    let mut parm = Vec::new();
+
+    for (key, value) in modinfo {
+        match key.as_str() {
+            "depends" => depends.extend(
+                value
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string()),
+            ),
+            "softdep" => softdep.push(value),
+            "alias" => alias.push(value),
+            "vermagic" => vermagic = Some(value),
+            "parm" => {
+                let (name, description) = value
+                    .split_once(':')
+                    .map(|(n, d)| (n.to_string(), d.to_string()))
+                    .unwrap_or((value, String::new()));
+                parm.push((name, description));
+            }
+            _ => {}
+        }
+    }
+
     Ok(ModuleBrief {
         name: path.file_name().unwrap().to_str().unwrap().to_string(),
         path: path.to_str().unwrap().to_string(),
@@ -136,11 +329,36 @@ fn read_to_module(path: PathBuf) -> Result<ModuleBrief> {
             .filter(|s| s.direction == SymbolDirection::References)
             .map(|s| s.name.clone())
             .collect(),
+        depends,
+        softdep,
+        alias,
+        vermagic,
+        parm,
     })
 }
 
-fn print_module_dependency_tree(kernel_path: &str, modules_pattern: &str, module_name: &str) {
-    let kernel_brief = read_to_module(PathBuf::from(kernel_path)).unwrap();
+/// Split a `.modinfo` section's raw bytes into its NUL-separated `key=value` entries.
+fn parse_modinfo_section(data: &[u8]) -> Vec<(String, String)> {
+    data.split(|&b| b == 0)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            entry
+                .split_once('=')
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+/// Collects every module matched by `modules_pattern` plus the kernel image
+/// itself (needed as a synthetic provider of kernel-exported symbols).
+/// Returns the combined set along with the kernel image's module name so
+/// callers can identify and exclude it without hardcoding its file name.
+fn collect_modules(kernel_path: &str, modules_pattern: &str) -> Result<(Vec<ModuleBrief>, String)> {
+    let kernel_brief = read_to_module(PathBuf::from(kernel_path))
+        .map_err(|e| anyhow::anyhow!("{}: failed to read kernel image ({})", kernel_path, e))?;
+    let kernel_name = kernel_brief.name.clone();
+
     let modules_glob_pattern = modules_pattern.to_string();
     let kernel_modules: Vec<ModuleBrief> = glob(modules_glob_pattern.as_str())
         .expect("Failed to read glob pattern")
@@ -159,16 +377,343 @@ fn print_module_dependency_tree(kernel_path: &str, modules_pattern: &str, module
         })
         .collect();
 
-    let kernel_plus_all_modules = [&kernel_modules[..], &[kernel_brief]].concat();
+    Ok(([&kernel_modules[..], &[kernel_brief]].concat(), kernel_name))
+}
+
+/// Resolve `target` to a canonical module name, accepting an exact file name, a
+/// bare module name (dashes/underscores and compression suffixes ignored), or
+/// a modalias such as `pci:v00008086d*` matched against declared `alias=` entries.
+fn resolve_target_name(all_modules: &[ModuleBrief], target: &str) -> Result<String> {
+    if let Some(m) = all_modules.iter().find(|m| m.name == target) {
+        return Ok(m.name.clone());
+    }
 
-    let wireguard_module_tree =
-        resolve_dependency_tree(kernel_plus_all_modules, module_name.to_string());
+    if let Some(m) = all_modules
+        .iter()
+        .find(|m| strip_module_suffix(&m.name) == strip_module_suffix(target))
+    {
+        return Ok(m.name.clone());
+    }
+
+    for module in all_modules {
+        for pattern in &module.alias {
+            if glob::Pattern::new(pattern).is_ok_and(|p| p.matches(target)) {
+                return Ok(module.name.clone());
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!("no module found matching '{}'", target))
+}
+
+fn print_module_dependency_tree(
+    kernel_path: &str,
+    modules_pattern: &str,
+    module_name: &str,
+) -> Result<()> {
+    let (all_modules, kernel_name) = collect_modules(kernel_path, modules_pattern)?;
+    let target_name = resolve_target_name(&all_modules, module_name)?;
+
+    let wireguard_module_tree = DependencyGraph::build(all_modules).resolve(&target_name)?;
 
     for module in wireguard_module_tree {
-        if module.name != "vmlinux" {
+        if module.name != kernel_name {
             println!("{}", module.path);
         }
     }
+
+    Ok(())
+}
+
+/// Names in `/proc/modules` have compression suffixes stripped and dashes
+/// normalized to underscores, the same way the kernel reports them.
+fn strip_module_suffix(file_name: &str) -> String {
+    file_name
+        .trim_end_matches(".gz")
+        .trim_end_matches(".xz")
+        .trim_end_matches(".zst")
+        .trim_end_matches(".lz4")
+        .trim_end_matches(".ko")
+        .replace('-', "_")
+}
+
+fn currently_loaded_modules() -> collections::HashSet<String> {
+    let listing = fs::read_to_string("/proc/modules").unwrap_or_default();
+    live::parse_module_listing(&listing)
+        .into_iter()
+        .filter(|m| m.is_live())
+        .map(|m| m.name().to_string())
+        .collect()
+}
+
+fn module_load_error(module_name: &str, err: std::io::Error) -> anyhow::Error {
+    match err.raw_os_error() {
+        Some(libc::EEXIST) => anyhow::anyhow!("{}: already loaded", module_name),
+        Some(libc::ENOKEY) => anyhow::anyhow!(
+            "{}: required module signature is missing or unverifiable",
+            module_name
+        ),
+        Some(libc::ENOEXEC) => anyhow::anyhow!("{}: invalid module format", module_name),
+        Some(libc::ENODEV) => {
+            anyhow::anyhow!("{}: unknown device/hardware not present", module_name)
+        }
+        _ => anyhow::anyhow!("{}: failed to load module ({})", module_name, err),
+    }
+}
+
+/// Load a single module's image into the kernel, using `finit_module(2)` for
+/// raw ELF images (passing the open fd directly) and falling back to
+/// `init_module(2)` with a `decompress_module`-produced image otherwise.
+fn load_module(module: &ModuleBrief) -> Result<()> {
+    let path = PathBuf::from(&module.path);
+    let param_values = CString::new("")?;
+
+    let mut file = File::open(&path)?;
+    let mut magic = [0u8; 4];
+    let is_raw_elf = file.read(&mut magic).unwrap_or(0) == 4 && magic == *b"\x7fELF";
+
+    let result = if is_raw_elf {
+        unsafe {
+            libc::syscall(
+                libc::SYS_finit_module,
+                file.as_raw_fd(),
+                param_values.as_ptr(),
+                0,
+            ) as i32
+        }
+    } else {
+        let image = decompress_module(&path)?;
+        unsafe {
+            libc::syscall(
+                libc::SYS_init_module,
+                image.as_ptr() as *mut std::ffi::c_void,
+                image.len() as libc::c_ulong,
+                param_values.as_ptr(),
+            ) as i32
+        }
+    };
+
+    if result != 0 {
+        return Err(module_load_error(
+            &module.name,
+            std::io::Error::last_os_error(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolve `module_name`'s dependency tree and insert each module the
+/// kernel doesn't already have loaded, in order, acting as a `modprobe`
+/// replacement.
+fn modprobe(kernel_path: &str, modules_pattern: &str, module_name: &str) -> Result<()> {
+    let (all_modules, kernel_name) = collect_modules(kernel_path, modules_pattern)?;
+    let target_name = resolve_target_name(&all_modules, module_name)?;
+    let load_order = DependencyGraph::build(all_modules).resolve(&target_name)?;
+    let loaded = currently_loaded_modules();
+
+    for module in load_order {
+        if module.name == kernel_name {
+            continue;
+        }
+
+        let normalized_name = strip_module_suffix(&module.name);
+        if loaded.contains(&normalized_name) {
+            println!("{} already loaded, skipping", normalized_name);
+            continue;
+        }
+
+        load_module(&module)?;
+        println!("Loaded {}", normalized_name);
+    }
+
+    Ok(())
+}
+
+/// Scan the whole modules glob once and write a `modules.dep`-format index:
+/// each line is `path/to/foo.ko: path/to/bar.ko path/to/baz.ko`, listing that
+/// module's full transitive dependencies in load order.
+fn depmod(kernel_path: &str, modules_pattern: &str, output_path: &str) -> Result<()> {
+    let (all_modules, kernel_name) = collect_modules(kernel_path, modules_pattern)?;
+    let graph = DependencyGraph::build(all_modules);
+
+    let mut entries = 0usize;
+    let mut lines: Vec<String> = Vec::new();
+    for module in &graph.modules {
+        if module.name == kernel_name {
+            continue;
+        }
+
+        let order = match graph.resolve(&module.name) {
+            Ok(order) => order,
+            Err(e) => {
+                println!("Skipping {}: {}", module.name, e);
+                continue;
+            }
+        };
+        let deps: Vec<&str> = order
+            .iter()
+            .filter(|m| m.name != module.name && m.name != kernel_name)
+            .map(|m| m.path.as_str())
+            .collect();
+
+        lines.push(format!("{}: {}", module.path, deps.join(" ")));
+        entries += 1;
+    }
+
+    fs::write(output_path, lines.join("\n") + "\n")?;
+    println!("Wrote {} module entries to {}", entries, output_path);
+
+    Ok(())
+}
+
+/// Walk `/proc/modules`'s "Used by" (`dependents`) column transitively from
+/// `target` to find everything that must come out first, then order that set
+/// so a module is only unloaded once nothing left in the set still uses it.
+fn compute_unload_order(modules: &[live::KernelModule], target: &str) -> Result<Vec<String>> {
+    let by_name: collections::HashMap<&str, &live::KernelModule> =
+        modules.iter().map(|m| (m.name(), m)).collect();
+
+    if !by_name.contains_key(target) {
+        return Err(anyhow::anyhow!("{}: not currently loaded", target));
+    }
+
+    let mut closure: collections::HashSet<String> = collections::HashSet::new();
+    let mut stack = vec![target.to_string()];
+    while let Some(name) = stack.pop() {
+        if closure.insert(name.clone()) {
+            if let Some(m) = by_name.get(name.as_str()) {
+                for dependent in m.dependents() {
+                    stack.push(dependent.clone());
+                }
+            }
+        }
+    }
+
+    // adjacency[d] = modules unblocked once dependent `d` is removed;
+    // in_degree[m] = how many modules still using `m` remain in the closure.
+    let mut adjacency: collections::HashMap<String, Vec<String>> = collections::HashMap::new();
+    let mut in_degree: collections::HashMap<String, usize> =
+        closure.iter().map(|name| (name.clone(), 0)).collect();
+
+    for name in &closure {
+        if let Some(m) = by_name.get(name.as_str()) {
+            for dependent in m.dependents() {
+                if closure.contains(dependent) {
+                    adjacency
+                        .entry(dependent.clone())
+                        .or_default()
+                        .push(name.clone());
+                    *in_degree.get_mut(name).unwrap() += 1;
+                }
+            }
+        }
+    }
+
+    let mut queue: collections::VecDeque<String> = closure
+        .iter()
+        .filter(|name| in_degree[*name] == 0)
+        .cloned()
+        .collect();
+
+    let mut order: Vec<String> = Vec::new();
+    while let Some(name) = queue.pop_front() {
+        order.push(name.clone());
+        if let Some(unblocked) = adjacency.get(&name) {
+            for next in unblocked {
+                let degree = in_degree.get_mut(next).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+    }
+
+    if order.len() != closure.len() {
+        return Err(anyhow::anyhow!(
+            "could not determine a safe unload order for '{}' (dependency cycle in /proc/modules?)",
+            target
+        ));
+    }
+
+    Ok(order)
+}
+
+fn module_unload_error(module_name: &str, err: std::io::Error) -> anyhow::Error {
+    match err.raw_os_error() {
+        Some(libc::ENOENT) => anyhow::anyhow!("{}: not currently loaded", module_name),
+        Some(libc::EWOULDBLOCK) => {
+            anyhow::anyhow!("{}: still in use, refusing to unload", module_name)
+        }
+        Some(libc::EBUSY) => anyhow::anyhow!("{}: module is busy", module_name),
+        _ => anyhow::anyhow!("{}: failed to unload module ({})", module_name, err),
+    }
+}
+
+/// Unload a single module, re-reading `/proc/modules` to refuse the removal
+/// if the module is still referenced or still has a live dependent.
+fn unload_one_module(name: &str) -> Result<()> {
+    let listing = fs::read_to_string("/proc/modules")?;
+    let modules = live::parse_module_listing(&listing);
+
+    let module = modules
+        .iter()
+        .find(|m| m.name() == name)
+        .ok_or_else(|| anyhow::anyhow!("{}: no longer loaded", name))?;
+
+    if module.refs() > 0 {
+        return Err(anyhow::anyhow!(
+            "{}: refusing to remove, still referenced ({} users)",
+            name,
+            module.refs()
+        ));
+    }
+
+    let has_live_dependent = module.dependents().iter().any(|dependent| {
+        modules
+            .iter()
+            .find(|m| m.name() == dependent)
+            .map(|m| m.is_live())
+            .unwrap_or(false)
+    });
+    if has_live_dependent {
+        return Err(anyhow::anyhow!(
+            "{}: refusing to remove, still has live dependents",
+            name
+        ));
+    }
+
+    let address = module.address();
+    let module_name = CString::new(name)?;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_delete_module,
+            module_name.as_ptr(),
+            libc::O_NONBLOCK,
+        ) as i32
+    };
+    if result != 0 {
+        return Err(module_unload_error(name, std::io::Error::last_os_error()));
+    }
+
+    println!("Removed {} (was loaded at {:#x})", name, address);
+
+    Ok(())
+}
+
+/// Unload `target` and, in reverse-topological order, every module still
+/// using it, via `delete_module(2)`.
+fn rmmod(target: &str) -> Result<()> {
+    let listing = fs::read_to_string("/proc/modules")?;
+    let modules = live::parse_module_listing(&listing);
+    let order = compute_unload_order(&modules, target)?;
+
+    for name in order {
+        unload_one_module(&name)?;
+    }
+
+    Ok(())
 }
 
 fn main() {
@@ -176,8 +721,45 @@ fn main() {
         .author("Isaac Parker, isaac@linux.com")
         .version("0.1.0")
         .about("Linux kernel module utility")
-        .subcommand(Command::new("modprobe").about("Load a module"))
+        .subcommand(
+            Command::new("modprobe")
+                .about("Load a module and its dependencies")
+                .args(vec![
+                    Arg::new("kernel")
+                        .short('k')
+                        .long("kernel")
+                        .default_value("/boot/vmlinuz"),
+                    Arg::new("modules")
+                        .short('m')
+                        .long("modules")
+                        .default_value("/lib/modules/*/kernel/**/*.ko"),
+                    Arg::new("target").short('t').long("target").required(true),
+                ]),
+        )
         .subcommand(Command::new("lsmod").about("List loaded modules"))
+        .subcommand(
+            Command::new("rmmod")
+                .about("Unload a module and any live modules depending on it")
+                .arg(Arg::new("target").short('t').long("target").required(true)),
+        )
+        .subcommand(
+            Command::new("depmod")
+                .about("Generate a modules.dep-style dependency database")
+                .args(vec![
+                    Arg::new("kernel")
+                        .short('k')
+                        .long("kernel")
+                        .default_value("/boot/vmlinuz"),
+                    Arg::new("modules")
+                        .short('m')
+                        .long("modules")
+                        .default_value("/lib/modules/*/kernel/**/*.ko"),
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .default_value("modules.dep"),
+                ]),
+        )
         .subcommand(Command::new("modinspect").args(vec![
                 Arg::new("kernel")
                     .short('k')
@@ -198,8 +780,51 @@ fn main() {
         Some(("lsmod", _)) => {
             live::parse_module_listing(fs::read_to_string("/proc/modules").unwrap().as_str());
         }
-        Some(("modprobe", _)) => {
-            panic!("Not yet implemented");
+        Some(("modprobe", args)) => {
+            let kernel = args
+                .get_one::<String>("kernel")
+                .ok_or("No kernel path provided")
+                .unwrap();
+            let modules = args
+                .get_one::<String>("modules")
+                .ok_or("No modules path provided")
+                .unwrap();
+            let target = args
+                .get_one::<String>("target")
+                .ok_or("No target module provided")
+                .unwrap();
+            if let Err(e) = modprobe(kernel.as_str(), modules.as_str(), target.as_str()) {
+                eprintln!("modprobe: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(("rmmod", args)) => {
+            let target = args
+                .get_one::<String>("target")
+                .ok_or("No target module provided")
+                .unwrap();
+            if let Err(e) = rmmod(target.as_str()) {
+                eprintln!("rmmod: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some(("depmod", args)) => {
+            let kernel = args
+                .get_one::<String>("kernel")
+                .ok_or("No kernel path provided")
+                .unwrap();
+            let modules = args
+                .get_one::<String>("modules")
+                .ok_or("No modules path provided")
+                .unwrap();
+            let output = args
+                .get_one::<String>("output")
+                .ok_or("No output path provided")
+                .unwrap();
+            if let Err(e) = depmod(kernel.as_str(), modules.as_str(), output.as_str()) {
+                eprintln!("depmod: {}", e);
+                std::process::exit(1);
+            }
         }
         Some(("modinspect", args)) => {
             let kernel = args
@@ -214,10 +839,198 @@ fn main() {
                 .get_one::<String>("target")
                 .ok_or("No target module provided")
                 .unwrap();
-            print_module_dependency_tree(kernel.as_str(), modules.as_str(), target.as_str());
+            if let Err(e) =
+                print_module_dependency_tree(kernel.as_str(), modules.as_str(), target.as_str())
+            {
+                eprintln!("modinspect: {}", e);
+                std::process::exit(1);
+            }
         }
         _ => {
             println!("No subcommand");
         }
     }
 }
+
+#[cfg(test)]
+fn test_module(name: &str) -> ModuleBrief {
+    ModuleBrief {
+        name: name.to_string(),
+        path: format!("/lib/modules/{}", name),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_resolve_reports_dependency_cycle() {
+    let mut a = test_module("a.ko");
+    a.depends = vec!["b".to_string()];
+    let mut b = test_module("b.ko");
+    b.depends = vec!["a".to_string()];
+
+    let result = DependencyGraph::build(vec![a, b]).resolve("a.ko");
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("cycle"));
+}
+
+#[test]
+fn test_resolve_prefers_explicit_depends_over_symbol_matching() {
+    let mut target = test_module("net.ko");
+    target.depends = vec!["core".to_string()];
+    target.references_symbols = vec!["decoy_symbol".to_string()];
+
+    let mut core = test_module("core.ko");
+    core.provides_symbols = vec!["core_symbol".to_string()];
+
+    let mut decoy = test_module("decoy.ko");
+    decoy.provides_symbols = vec!["decoy_symbol".to_string()];
+
+    let order: Vec<String> = DependencyGraph::build(vec![target, core, decoy])
+        .resolve("net.ko")
+        .unwrap()
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+
+    // The explicit `depends=` list names "core", so "decoy" (which only
+    // matches via the symbol-matching fallback) must not appear at all.
+    assert_eq!(order, vec!["core.ko".to_string(), "net.ko".to_string()]);
+}
+
+#[test]
+fn test_resolve_falls_back_to_symbol_matching_without_depends() {
+    let mut target = test_module("net.ko");
+    target.references_symbols = vec!["core_symbol".to_string()];
+
+    let mut core = test_module("core.ko");
+    core.provides_symbols = vec!["core_symbol".to_string()];
+
+    let order: Vec<String> = DependencyGraph::build(vec![target, core])
+        .resolve("net.ko")
+        .unwrap()
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+
+    assert_eq!(order, vec!["core.ko".to_string(), "net.ko".to_string()]);
+}
+
+#[test]
+fn test_parse_modinfo_section() {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"depends=core,net");
+    data.push(0);
+    data.extend_from_slice(b"alias=pci:v00008086d*");
+    data.push(0);
+    data.extend_from_slice(b"vermagic=6.1.0 SMP mod_unload");
+    data.push(0);
+
+    let entries = parse_modinfo_section(&data);
+
+    assert_eq!(
+        entries,
+        vec![
+            ("depends".to_string(), "core,net".to_string()),
+            ("alias".to_string(), "pci:v00008086d*".to_string()),
+            ("vermagic".to_string(), "6.1.0 SMP mod_unload".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_detect_module_format_by_magic_bytes() {
+    let path = Path::new("module.bin");
+    assert_eq!(
+        detect_module_format(path, &[0x1f, 0x8b, 0x08, 0x00]),
+        ModuleFormat::Gzip
+    );
+    assert_eq!(
+        detect_module_format(path, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+        ModuleFormat::Xz
+    );
+    assert_eq!(
+        detect_module_format(path, &[0x28, 0xb5, 0x2f, 0xfd]),
+        ModuleFormat::Zstd
+    );
+    assert_eq!(
+        detect_module_format(path, &[0x04, 0x22, 0x4d, 0x18]),
+        ModuleFormat::Lz4
+    );
+    assert_eq!(
+        detect_module_format(path, &[0x7f, b'E', b'L', b'F']),
+        ModuleFormat::Raw
+    );
+    assert_eq!(
+        detect_module_format(path, &[0x00, 0x01, 0x02, 0x03]),
+        ModuleFormat::Unknown
+    );
+}
+
+#[test]
+fn test_decompress_module_round_trips_each_format() {
+    use std::io::Write;
+
+    let payload = b"this is a fake .ko for testing".to_vec();
+    let dir = std::env::temp_dir().join(format!(
+        "kernel-module-sort-test-{:?}",
+        std::thread::current().id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+
+    let gz_path = dir.join("module.ko.gz");
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&payload).unwrap();
+    fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+    assert_eq!(decompress_module(&gz_path).unwrap(), payload);
+
+    let xz_path = dir.join("module.ko.xz");
+    let mut encoder = xz::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(&payload).unwrap();
+    fs::write(&xz_path, encoder.finish().unwrap()).unwrap();
+    assert_eq!(decompress_module(&xz_path).unwrap(), payload);
+
+    let zst_path = dir.join("module.ko.zst");
+    fs::write(&zst_path, zstd::encode_all(&payload[..], 0).unwrap()).unwrap();
+    assert_eq!(decompress_module(&zst_path).unwrap(), payload);
+
+    let lz4_path = dir.join("module.ko.lz4");
+    let mut encoder = lz4::EncoderBuilder::new().build(Vec::new()).unwrap();
+    encoder.write_all(&payload).unwrap();
+    let (buf, result) = encoder.finish();
+    result.unwrap();
+    fs::write(&lz4_path, buf).unwrap();
+    assert_eq!(decompress_module(&lz4_path).unwrap(), payload);
+
+    let garbage_path = dir.join("module.bin");
+    fs::write(&garbage_path, [0x00, 0x01, 0x02, 0x03]).unwrap();
+    assert!(decompress_module(&garbage_path).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compute_unload_order_walks_dependent_chain() {
+    let listing = "core_mod 16384 1 mid_mod, Live 0x0000000000000000
+mid_mod 16384 1 leaf_mod, Live 0x0000000000000000
+leaf_mod 16384 0 - Live 0x0000000000000000
+";
+    let modules = live::parse_module_listing(listing);
+
+    let order = compute_unload_order(&modules, "core_mod").unwrap();
+
+    assert_eq!(order, vec!["leaf_mod", "mid_mod", "core_mod"]);
+}
+
+#[test]
+fn test_compute_unload_order_reports_dependent_cycle() {
+    let listing = "a_mod 16384 1 b_mod, Live 0x0000000000000000
+b_mod 16384 1 a_mod, Live 0x0000000000000000
+";
+    let modules = live::parse_module_listing(listing);
+
+    let result = compute_unload_order(&modules, "a_mod");
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("cycle"));
+}